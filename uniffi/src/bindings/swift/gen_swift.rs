@@ -0,0 +1,240 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Renders a `ComponentInterface` as Swift: the C bridging header, the Clang modulemap
+//! that exposes it, and the high-level `.swift` wrapper that calls through it.
+
+use askama::Template;
+
+use super::super::super::interface::{ComponentInterface, Function, Type};
+
+/// Per-component settings threaded through every template below. Currently just the
+/// namespace, but it's the natural place to grow e.g. a custom Swift module name.
+#[derive(Clone)]
+pub struct Config {
+    pub namespace: String,
+}
+
+impl From<&ComponentInterface> for Config {
+    fn from(ci: &ComponentInterface) -> Self {
+        Config {
+            namespace: ci.namespace().to_string(),
+        }
+    }
+}
+
+/// True if any function this component exports is `async`.
+pub fn has_async_fns(ci: &ComponentInterface) -> bool {
+    ci.function_definitions().iter().any(Function::is_async)
+}
+
+#[derive(Template)]
+#[template(syntax = "c", ext = "h", source = "
+#include <stdint.h>
+
+{% for decl in self.function_declarations() %}
+{{ decl }}
+{% endfor %}
+")]
+pub struct BridgingHeader<'a> {
+    #[allow(dead_code)]
+    config: &'a Config,
+    ci: &'a ComponentInterface,
+}
+
+impl<'a> BridgingHeader<'a> {
+    pub fn new(config: &'a Config, ci: &'a ComponentInterface) -> Self {
+        Self { config, ci }
+    }
+
+    /// One C declaration per exported function. A sync function declares its single
+    /// FFI entry point as before; an async function instead declares the four calls
+    /// that drive its Rust future to completion: the call that starts it (returning a
+    /// handle), `_poll` (arm a callback for the next wake-up), `_complete` (read the
+    /// finished result) and `_free` (release the future).
+    fn function_declarations(&self) -> Vec<String> {
+        self.ci
+            .function_definitions()
+            .iter()
+            .map(|func| {
+                let ffi_name = func.ffi_func().name();
+                let args = c_arg_list(func);
+                if func.is_async() {
+                    let ret = c_type_name(func.return_type());
+                    format!(
+                        "void* {ffi_name}({args}RustCallStatus *out_status);\n\
+                         void {ffi_name}_poll(void* handle, void (*callback)(void* callback_data, int8_t), void* callback_data);\n\
+                         {ret} {ffi_name}_complete(void* handle, RustCallStatus *out_status);\n\
+                         void {ffi_name}_free(void* handle);"
+                    )
+                } else {
+                    let ret = c_type_name(func.return_type());
+                    format!("{ret} {ffi_name}({args}RustCallStatus *out_status);")
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Template)]
+#[template(syntax = "swift", ext = "swift", source = "
+{% for decl in self.function_wrappers() %}
+{{ decl }}
+{% endfor %}
+")]
+pub struct SwiftWrapper<'a> {
+    #[allow(dead_code)]
+    config: &'a Config,
+    ci: &'a ComponentInterface,
+}
+
+impl<'a> SwiftWrapper<'a> {
+    pub fn new(config: &'a Config, ci: &'a ComponentInterface) -> Self {
+        Self { config, ci }
+    }
+
+    /// One public Swift function per exported function. Async functions get a
+    /// Swift `async throws` signature that drives the Rust future via
+    /// `uniffiRustCallAsync` (see `templates/Async.swift`) instead of calling
+    /// straight through to a blocking FFI function.
+    fn function_wrappers(&self) -> Vec<String> {
+        self.ci
+            .function_definitions()
+            .iter()
+            .map(|func| {
+                let name = func.name();
+                let ffi_name = func.ffi_func().name();
+                let args = swift_arg_list(func);
+                let call_args = swift_call_arg_list(func);
+                let ret = swift_type_name(func.return_type());
+                if func.is_async() {
+                    format!(
+                        "public func {name}({args}) async throws -> {ret} {{\n\
+                        \u{20}   var status = RustCallStatus()\n\
+                        \u{20}   let handle = {ffi_name}({call_args}&status)\n\
+                        \u{20}   try rustCallStatusCheck(status)\n\
+                        \u{20}   return try await uniffiRustCallAsync(\n\
+                        \u{20}       pollFunc: {{ callback, callbackData in {ffi_name}_poll(handle, callback, callbackData) }},\n\
+                        \u{20}       completeFunc: {{\n\
+                        \u{20}           var status = RustCallStatus()\n\
+                        \u{20}           let result = {ffi_name}_complete(handle, &status)\n\
+                        \u{20}           try rustCallStatusCheck(status)\n\
+                        \u{20}           return result\n\
+                        \u{20}       }},\n\
+                        \u{20}       freeFunc: {{ {ffi_name}_free(handle) }}\n\
+                        \u{20}   )\n\
+                        }}"
+                    )
+                } else {
+                    format!(
+                        "public func {name}({args}) -> {ret} {{\n\
+                        \u{20}   var status = RustCallStatus()\n\
+                        \u{20}   let result = {ffi_name}({call_args}&status)\n\
+                        \u{20}   try! rustCallStatusCheck(status)\n\
+                        \u{20}   return result\n\
+                        }}"
+                    )
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Template)]
+#[template(
+    syntax = "modulemap",
+    ext = "modulemap",
+    source = "module {{ module_name }} {
+    header \"{{ module_name }}.h\"
+    export *
+}
+"
+)]
+pub struct ModuleMap<'a> {
+    #[allow(dead_code)]
+    config: &'a Config,
+    #[allow(dead_code)]
+    ci: &'a ComponentInterface,
+    module_name: &'a str,
+}
+
+impl<'a> ModuleMap<'a> {
+    pub fn new(config: &'a Config, ci: &'a ComponentInterface, module_name: &'a str) -> Self {
+        Self {
+            config,
+            ci,
+            module_name,
+        }
+    }
+}
+
+fn c_arg_list(func: &Function) -> String {
+    let mut out = String::new();
+    for arg in func.arguments() {
+        out.push_str(&format!("{} {}, ", c_type_name(Some(&arg.type_())), arg.name()));
+    }
+    out
+}
+
+fn swift_arg_list(func: &Function) -> String {
+    func.arguments()
+        .iter()
+        .map(|arg| format!("{}: {}", arg.name(), swift_type_name(Some(&arg.type_()))))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn swift_call_arg_list(func: &Function) -> String {
+    let mut out = String::new();
+    for arg in func.arguments() {
+        out.push_str(&format!("{}, ", arg.name()));
+    }
+    out
+}
+
+/// Map a UniFFI type to the C type used for it in the bridging header. Falls back to
+/// `RustBuffer` (with the canonical name in a trailing C comment) for anything not
+/// explicitly called out here, which is enough for the scalar types that make up most
+/// async function signatures.
+fn c_type_name(ty: Option<&Type>) -> String {
+    match ty.map(Type::canonical_name) {
+        None => "void".to_string(),
+        Some(name) => match name.as_str() {
+            "u8" => "uint8_t".to_string(),
+            "i8" => "int8_t".to_string(),
+            "u16" => "uint16_t".to_string(),
+            "i16" => "int16_t".to_string(),
+            "u32" => "uint32_t".to_string(),
+            "i32" => "int32_t".to_string(),
+            "u64" => "uint64_t".to_string(),
+            "i64" => "int64_t".to_string(),
+            "f32" => "float".to_string(),
+            "f64" => "double".to_string(),
+            "bool" => "int8_t".to_string(),
+            other => format!("RustBuffer /* {other} */"),
+        },
+    }
+}
+
+/// Map a UniFFI type to the Swift type used for it in the generated wrapper.
+fn swift_type_name(ty: Option<&Type>) -> String {
+    match ty.map(Type::canonical_name) {
+        None => "Void".to_string(),
+        Some(name) => match name.as_str() {
+            "u8" => "UInt8".to_string(),
+            "i8" => "Int8".to_string(),
+            "u16" => "UInt16".to_string(),
+            "i16" => "Int16".to_string(),
+            "u32" => "UInt32".to_string(),
+            "i32" => "Int32".to_string(),
+            "u64" => "UInt64".to_string(),
+            "i64" => "Int64".to_string(),
+            "f32" => "Float".to_string(),
+            "f64" => "Double".to_string(),
+            "bool" => "Bool".to_string(),
+            "string" => "String".to_string(),
+            other => other.to_string(),
+        },
+    }
+}
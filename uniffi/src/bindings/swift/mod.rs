@@ -3,6 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use std::{
+    collections::HashMap,
     ffi::OsString,
     fs,
     fs::File,
@@ -11,71 +12,173 @@ use std::{
 };
 
 use anyhow::Result;
-use anyhow::{anyhow, bail};
+use anyhow::{anyhow, bail, Context};
 
 pub mod gen_swift;
+pub mod xcframework;
 pub use gen_swift::{BridgingHeader, Config, ModuleMap, SwiftWrapper};
+pub use xcframework::create_xcframework;
 
 use super::super::interface::ComponentInterface;
+use super::super::macro_metadata;
 
 pub struct Bindings {
+    module_name: String,
     header: String,
+    modulemap: String,
     library: String,
 }
 
+/// Name of the low-level Clang module that carries the C FFI for `namespace`.
+///
+/// Every generated `<namespace>.swift` does `import <namespace>FFI` to pull in the
+/// C declarations from `<namespace>FFI.h`, via the sibling `<namespace>FFI.modulemap`.
+fn ffi_module_name(namespace: &str) -> String {
+    format!("{namespace}FFI")
+}
+
 pub fn write_bindings(ci: &ComponentInterface, out_dir: &Path) -> Result<()> {
     let out_path = PathBuf::from(out_dir);
+    fs::create_dir_all(&out_path)?;
 
-    // We're going to generate an "umbrella header" declaration for the swift module,
-    // and swift doesn't like having multiple umbrella headers in the same directory.
-    // Work around this by creating a subdirectory for each uniffi component.
-    // Probably there's a better way to do this...?
-    let mut module_dir = out_path.clone();
-    module_dir.push(format!("{}.swiftmodule-dir", ci.namespace()));
-    fs::create_dir_all(&module_dir)?;
+    let Bindings {
+        module_name,
+        header,
+        modulemap,
+        library,
+    } = generate_bindings(ci)?;
 
-    let mut module_map_file = module_dir.clone();
-    module_map_file.push("uniffi.modulemap");
+    let mut header_file = out_path.clone();
+    header_file.push(format!("{module_name}.h"));
 
-    let mut header_file = module_dir.clone();
-    header_file.push(format!("{}-Bridging-Header.h", ci.namespace()));
+    let mut module_map_file = out_path.clone();
+    module_map_file.push(format!("{module_name}.modulemap"));
 
     let mut source_file = out_path;
     source_file.push(format!("{}.swift", ci.namespace()));
 
-    let Bindings { header, library } = generate_bindings(&ci)?;
-
     let mut h = File::create(&header_file)?;
     write!(h, "{}", header)?;
 
     let mut m = File::create(&module_map_file)?;
-    write!(m, "{}", generate_module_map(&ci, &header_file)?)?;
+    write!(m, "{}", modulemap)?;
 
     let mut l = File::create(&source_file)?;
     write!(l, "{}", library)?;
 
+    if gen_swift::has_async_fns(ci) {
+        write_async_runtime_shim(&out_path)?;
+    }
+
     Ok(())
 }
 
+/// Swift runtime shim that drives a Rust poll-based future to completion from a Swift
+/// `async` function, via `withCheckedThrowingContinuation`. Written alongside a component's own
+/// `.swift` file whenever it exports at least one `async` function, since every such
+/// function's generated wrapper calls into it.
+const ASYNC_RUNTIME_SHIM: &str = include_str!("templates/Async.swift");
+
+fn write_async_runtime_shim(out_dir: &Path) -> Result<()> {
+    let mut path = PathBuf::from(out_dir);
+    path.push("Async.swift");
+    fs::write(path, ASYNC_RUNTIME_SHIM)?;
+    Ok(())
+}
+
+/// Generate Swift bindings for every UniFFI component exported by a built cdylib.
+///
+/// Unlike [`write_bindings`], which needs a single already-parsed `ComponentInterface`.
+pub fn generate_bindings_from_library(cdylib_path: &Path, out_dir: &Path) -> Result<()> {
+    let cis = find_components(cdylib_path)?;
+    for ci in &cis {
+        write_bindings(ci, out_dir)?;
+    }
+    Ok(())
+}
+
+/// Discover every `ComponentInterface` exported by `cdylib_path`.
+///
+/// The dylib's exported symbols carry the same metadata that a UDL-driven build would
+/// otherwise produce from parsing a `.udl` file. We group that metadata by the crate
+/// that produced it, turn each group into a `ComponentInterface`, then let every
+/// component see its siblings so that `External` type references across crates in
+/// the same dylib resolve without the caller naming them explicitly.
+fn find_components(cdylib_path: &Path) -> Result<Vec<ComponentInterface>> {
+    let metadata = macro_metadata::extract_from_library(cdylib_path)
+        .with_context(|| format!("failed to extract UniFFI metadata from {cdylib_path:?}"))?;
+
+    let crate_names = cargo_crate_names()?;
+
+    let mut by_crate: HashMap<String, Vec<_>> = HashMap::new();
+    for item in metadata {
+        by_crate.entry(item.crate_name().to_string()).or_default().push(item);
+    }
+
+    let mut cis = Vec::new();
+    for (crate_name, items) in by_crate {
+        let mut ci = ComponentInterface::from_metadata(items)
+            .with_context(|| format!("failed to build component interface for crate `{crate_name}`"))?;
+        if let Some(package_name) = crate_names.get(&crate_name) {
+            ci.set_package_name(package_name.clone());
+        }
+        cis.push(ci);
+    }
+
+    // Now that every component in the dylib is known, let each one resolve its
+    // `External` type references against the full set instead of failing closed.
+    let all = cis.clone();
+    for ci in &mut cis {
+        ci.resolve_external_types(&all)?;
+    }
+
+    Ok(cis)
+}
+
+/// Map Rust crate name (as embedded in the dylib's metadata, e.g. `my_crate`) -> Cargo
+/// package name (as it appears in `Cargo.toml`, which may contain hyphens, e.g.
+/// `my-crate`), for every package in the workspace of the current directory. The
+/// embedded metadata only ever sees the underscored crate name, so without this we'd
+/// never recover a package's real name whenever the two differ.
+fn cargo_crate_names() -> Result<HashMap<String, String>> {
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .exec()
+        .context("failed to run `cargo metadata`")?;
+    let package_names = metadata.workspace_packages().into_iter().map(|pkg| pkg.name.clone());
+    Ok(crate_names_from_package_names(package_names))
+}
+
+/// The actual hyphen/underscore mapping behind [`cargo_crate_names`], pulled out so it
+/// can be unit tested without shelling out to `cargo metadata`.
+fn crate_names_from_package_names(
+    package_names: impl IntoIterator<Item = String>,
+) -> HashMap<String, String> {
+    package_names
+        .into_iter()
+        .map(|name| (name.replace('-', "_"), name))
+        .collect()
+}
+
 /// Generate Swift bindings for the given ComponentInterface, as a string.
 pub fn generate_bindings(ci: &ComponentInterface) -> Result<Bindings> {
     let config = Config::from(&ci);
+    let module_name = ffi_module_name(ci.namespace());
     use askama::Template;
-    let header = BridgingHeader::new(&config, &ci)
+    let header = BridgingHeader::new(&config, ci)
         .render()
         .map_err(|_| anyhow!("failed to render Swift bridging header"))?;
-    let library = SwiftWrapper::new(&config, &ci)
-        .render()
-        .map_err(|_| anyhow!("failed to render Swift library"))?;
-    Ok(Bindings { header, library })
-}
-
-fn generate_module_map(ci: &ComponentInterface, header_path: &Path) -> Result<String> {
-    use askama::Template;
-    let module_map = ModuleMap::new(&ci, header_path)
+    let modulemap = ModuleMap::new(&config, ci, &module_name)
         .render()
         .map_err(|_| anyhow!("failed to render Swift module map"))?;
-    Ok(module_map)
+    let library = SwiftWrapper::new(&config, ci)
+        .render()
+        .map_err(|_| anyhow!("failed to render Swift library"))?;
+    Ok(Bindings {
+        module_name,
+        header,
+        modulemap,
+        library,
+    })
 }
 
 /// ...
@@ -83,8 +186,7 @@ pub fn compile_bindings(ci: &ComponentInterface, out_dir: &Path) -> Result<()> {
     let out_path = PathBuf::from(out_dir);
 
     let mut module_map_file = out_path.clone();
-    module_map_file.push(format!("{}.swiftmodule-dir", ci.namespace()));
-    module_map_file.push("uniffi.modulemap");
+    module_map_file.push(format!("{}.modulemap", ffi_module_name(ci.namespace())));
 
     let mut module_map_file_option = OsString::from("-fmodule-map-file=");
     module_map_file_option.push(module_map_file.as_os_str());
@@ -100,8 +202,8 @@ pub fn compile_bindings(ci: &ComponentInterface, out_dir: &Path) -> Result<()> {
     // symbols" when we try to import the module.
     // See https://bugs.swift.org/browse/SR-1191.
 
-    let status = std::process::Command::new("swiftc")
-        .arg("-module-name")
+    let mut cmd = std::process::Command::new("swiftc");
+    cmd.arg("-module-name")
         .arg(ci.namespace())
         .arg("-emit-library")
         .arg("-o")
@@ -115,46 +217,178 @@ pub fn compile_bindings(ci: &ComponentInterface, out_dir: &Path) -> Result<()> {
         .arg(format!("-luniffi_{}", ci.namespace()))
         .arg("-Xcc")
         .arg(module_map_file_option)
-        .arg(source_file)
-        .spawn()?
-        .wait()?;
+        .arg(source_file);
+
+    if gen_swift::has_async_fns(ci) {
+        cmd.arg(out_path.join("Async.swift"));
+    }
+
+    let status = cmd.spawn()?.wait()?;
     if !status.success() {
         bail!("running `swiftc` failed")
     }
     Ok(())
 }
 
-pub fn run_script(out_dir: Option<&Path>, script_file: Option<&Path>) -> Result<()> {
+/// Options controlling how [`run_script`] invokes the `swift` interpreter.
+#[derive(Debug, Clone, Default)]
+pub struct RunScriptOptions {
+    /// Whether the `swift` process's stdout/stderr are inherited by this
+    /// process. When off, both are discarded: compiler diagnostics and the
+    /// script's own output alike.
+    pub show_compiler_messages: bool,
+}
+
+/// Run a Swift script against the bindings generated for `crate_name` in `out_dir`,
+/// forwarding `args` to the script after a `--` separator.
+pub fn run_script(
+    out_dir: &Path,
+    crate_name: &str,
+    script_file: &Path,
+    args: Vec<String>,
+    options: RunScriptOptions,
+) -> Result<()> {
     let mut cmd = std::process::Command::new("swift");
 
     // Find any module maps and/or dylibs in the target directory, and tell swift to use them.
-    if let Some(out_dir) = out_dir {
-        cmd.arg("-I").arg(out_dir).arg("-L").arg(out_dir);
-        for entry in PathBuf::from(out_dir).read_dir()? {
-            let entry = entry?;
-            if let Some(ext) = entry.path().extension() {
-                if ext == "swiftmodule-dir" {
-                    let mut module_map_file = PathBuf::from(entry.path());
-                    module_map_file.push("uniffi.modulemap");
-                    let mut option = OsString::from("-fmodule-map-file=");
-                    option.push(module_map_file);
-                    cmd.arg("-Xcc");
-                    cmd.arg(option);
-                } else if ext == "dylib" || ext == "so" {
-                    let mut option = OsString::from("-l");
-                    option.push(entry.path());
-                    cmd.arg(option);
-                }
+    cmd.arg("-I").arg(out_dir).arg("-L").arg(out_dir);
+    for entry in out_dir.read_dir()? {
+        let entry = entry?;
+        if let Some(ext) = entry.path().extension() {
+            if ext == "modulemap" {
+                let mut option = OsString::from("-fmodule-map-file=");
+                option.push(entry.path());
+                cmd.arg("-Xcc");
+                cmd.arg(option);
+            } else if ext == "dylib" || ext == "so" {
+                let mut option = OsString::from("-l");
+                option.push(entry.path());
+                cmd.arg(option);
             }
         }
     }
 
-    if let Some(script) = script_file {
-        cmd.arg(script);
+    cmd.arg(script_file);
+    if !args.is_empty() {
+        cmd.arg("--").args(args);
+    }
+
+    if !options.show_compiler_messages {
+        cmd.stdout(std::process::Stdio::null());
+        cmd.stderr(std::process::Stdio::null());
     }
 
-    if !cmd.spawn()?.wait()?.success() {
+    let status = cmd
+        .spawn()
+        .with_context(|| format!("failed to spawn `swift` for crate `{crate_name}`"))?
+        .wait()?;
+    if !status.success() {
         bail!("running `swift` failed")
     }
     Ok(())
 }
+
+/// Build and run one of a fixture crate's Swift test scripts, end to end.
+pub fn run_test(tmp_dir: &Path, fixture_name: &str, script_file: &Path) -> Result<()> {
+    let cdylib_name = format!("libuniffi_{fixture_name}.dylib");
+    let cdylib_path = target_dir()?.join(&cdylib_name);
+    if !cdylib_path.exists() {
+        bail!("{cdylib_path:?} does not exist - build the `{fixture_name}` fixture first");
+    }
+
+    let out_dir = tmp_dir.join(fixture_name);
+    fs::create_dir_all(&out_dir)?;
+    fs::copy(&cdylib_path, out_dir.join(&cdylib_name))?;
+
+    generate_bindings_from_library(&cdylib_path, &out_dir)?;
+    compile_swift_sources(&out_dir, fixture_name)?;
+
+    run_script(
+        &out_dir,
+        fixture_name,
+        script_file,
+        Vec::new(),
+        RunScriptOptions::default(),
+    )
+}
+
+/// Compile every `.swift` file in `out_dir` into a single module named after
+/// `crate_name`, alongside whatever `.modulemap`s [`write_bindings`] left there, and
+/// link against the `uniffi_<crate_name>` cdylib that [`run_test`] copied in.
+fn compile_swift_sources(out_dir: &Path, crate_name: &str) -> Result<()> {
+    let mut sources = Vec::new();
+    let mut cmd = std::process::Command::new("swiftc");
+
+    // Mirror run_script's module-map discovery so `import <name>FFI` resolves here too.
+    for entry in out_dir.read_dir()? {
+        let entry = entry?;
+        let path = entry.path();
+        if let Some(ext) = path.extension() {
+            if ext == "modulemap" {
+                let mut option = OsString::from("-fmodule-map-file=");
+                option.push(&path);
+                cmd.arg("-Xcc").arg(option);
+            } else if ext == "swift" {
+                sources.push(path);
+            }
+        }
+    }
+
+    let mut dylib_file = PathBuf::from(out_dir);
+    dylib_file.push(format!("lib{crate_name}.dylib"));
+
+    let status = cmd
+        .arg("-module-name")
+        .arg(crate_name)
+        .arg("-emit-library")
+        .arg("-o")
+        .arg(&dylib_file)
+        .arg("-emit-module")
+        .arg("-emit-module-path")
+        .arg(out_dir)
+        .arg("-parse-as-library")
+        .arg("-L")
+        .arg(out_dir)
+        .arg(format!("-luniffi_{crate_name}"))
+        .args(sources)
+        .spawn()?
+        .wait()?;
+    if !status.success() {
+        bail!("running `swiftc` failed")
+    }
+    Ok(())
+}
+
+/// Best-effort guess at the workspace's `target/debug` directory, for locating a
+/// fixture's already-built cdylib in [`run_test`].
+fn target_dir() -> Result<PathBuf> {
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .no_deps()
+        .exec()
+        .context("failed to run `cargo metadata`")?;
+    Ok(metadata.target_directory.join("debug").into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Mirrors how the other bindings' fixture tests are laid out: one test per
+    // fixture script, driven end-to-end through `run_test` instead of a shell script.
+    #[test]
+    fn test_coverall() {
+        let tmp_dir = std::env::temp_dir().join("uniffi-swift-bindings-tests");
+        let script_file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../fixtures/coverall/tests/bindings/test_coverall.swift");
+        run_test(&tmp_dir, "coverall", &script_file).unwrap();
+    }
+
+    #[test]
+    fn test_crate_names_from_package_names_resolves_hyphenated_names() {
+        let names = crate_names_from_package_names(
+            ["uniffi-core".to_string(), "simple_crate".to_string()].into_iter(),
+        );
+        assert_eq!(names.get("uniffi_core"), Some(&"uniffi-core".to_string()));
+        assert_eq!(names.get("simple_crate"), Some(&"simple_crate".to_string()));
+    }
+}
@@ -0,0 +1,179 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Bundle the output of [`generate_bindings_from_library`](super::generate_bindings_from_library)
+//! for several components into a single `.xcframework`, ready to embed in an Xcode project.
+
+use std::{collections::HashSet, fs, path::Path, path::PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+/// One compiled library slice (e.g. "macosx" or "iphonesimulator") to bundle
+/// into the xcframework, alongside the headers it was built against.
+pub struct XcframeworkSlice {
+    /// The `xcodebuild -create-xcframework -library` platform this slice targets,
+    /// e.g. `macosx`, `iphoneos`, or `iphonesimulator`.
+    pub platform: String,
+    /// Path to the static or dynamic library built for this platform.
+    pub library_path: PathBuf,
+}
+
+/// Merge the generated Swift output for every component in `component_out_dir` into
+/// `staging_dir`, then drive `xcodebuild -create-xcframework` over `slices` to produce
+/// `xcframework_path`.
+///
+/// `component_out_dir` is expected to already contain the `<name>FFI.h`,
+/// `<name>FFI.modulemap` and `<name>.swift` files that [`write_bindings`](super::write_bindings)
+/// (or [`generate_bindings_from_library`](super::generate_bindings_from_library)) produced for
+/// every component being bundled.
+pub fn create_xcframework(
+    component_out_dir: &Path,
+    slices: &[XcframeworkSlice],
+    staging_dir: &Path,
+    xcframework_path: &Path,
+) -> Result<()> {
+    if slices.is_empty() {
+        bail!("create_xcframework needs at least one slice to bundle");
+    }
+
+    let mut seen_platforms = HashSet::new();
+    for slice in slices {
+        if !seen_platforms.insert(slice.platform.as_str()) {
+            bail!(
+                "create_xcframework got two slices for platform `{}` - each platform \
+                 (e.g. macosx, iphoneos, iphonesimulator) may only appear once",
+                slice.platform
+            );
+        }
+    }
+
+    fs::create_dir_all(staging_dir)?;
+    let module_names = stage_component_output(component_out_dir, staging_dir)?;
+    write_umbrella_modulemap(staging_dir, &module_names)?;
+
+    if xcframework_path.exists() {
+        fs::remove_dir_all(xcframework_path)?;
+    }
+
+    // `xcodebuild -create-xcframework` tells slices apart by the architecture/platform
+    // baked into each library, not by a `-platform` flag, so we stage each slice's
+    // library under a directory named for its platform. That also keeps libraries that
+    // happen to share a file name (e.g. every slice producing `libexample.a`) from
+    // colliding when they're all copied next to each other.
+    for slice in slices {
+        let slice_dir = staging_dir.join(&slice.platform);
+        fs::create_dir_all(&slice_dir)?;
+        let file_name = slice.library_path.file_name().with_context(|| {
+            format!(
+                "library path for platform `{}` has no file name: {:?}",
+                slice.platform, slice.library_path
+            )
+        })?;
+        fs::copy(&slice.library_path, slice_dir.join(file_name))
+            .with_context(|| format!("failed to stage library for platform `{}`", slice.platform))?;
+    }
+
+    let mut cmd = std::process::Command::new("xcodebuild");
+    cmd.arg("-create-xcframework");
+    for slice in slices {
+        let slice_dir = staging_dir.join(&slice.platform);
+        let file_name = slice.library_path.file_name().expect("checked above");
+        cmd.arg("-library").arg(slice_dir.join(file_name));
+        cmd.arg("-headers").arg(staging_dir);
+    }
+    cmd.arg("-output").arg(xcframework_path);
+
+    let status = cmd
+        .spawn()
+        .context("failed to spawn `xcodebuild`")?
+        .wait()?;
+    if !status.success() {
+        bail!("running `xcodebuild -create-xcframework` failed")
+    }
+    Ok(())
+}
+
+/// Copy every generated `.swift` source and `.h` header from `component_out_dir` into
+/// `staging_dir`, returning the `<name>FFI` module names found along the way.
+fn stage_component_output(component_out_dir: &Path, staging_dir: &Path) -> Result<Vec<String>> {
+    let mut module_names = Vec::new();
+    for entry in fs::read_dir(component_out_dir)? {
+        let path = entry?.path();
+        let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+            continue;
+        };
+        match ext {
+            "swift" | "h" => {
+                let file_name = path
+                    .file_name()
+                    .expect("path from read_dir always has a file name");
+                fs::copy(&path, staging_dir.join(file_name))?;
+            }
+            "modulemap" => {
+                let stem = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .expect("modulemap file always has a file name");
+                module_names.push(stem.to_string());
+            }
+            _ => {}
+        }
+    }
+    Ok(module_names)
+}
+
+/// Write a single modulemap into `staging_dir` that re-exports every `<name>FFI` module
+/// found in the staged headers, so Xcode sees one coherent headers directory instead of
+/// N competing per-component modulemaps.
+fn write_umbrella_modulemap(staging_dir: &Path, module_names: &[String]) -> Result<()> {
+    let mut contents = String::new();
+    for module_name in module_names {
+        contents.push_str(&format!(
+            "module {module_name} {{\n    header \"{module_name}.h\"\n    export *\n}}\n\n"
+        ));
+    }
+    fs::write(staging_dir.join("module.modulemap"), contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // The duplicate-platform check runs before anything touches the filesystem or
+    // shells out to `xcodebuild`, so it's exercisable without a real Xcode toolchain.
+    #[test]
+    fn test_create_xcframework_rejects_duplicate_platforms() {
+        let slices = vec![
+            XcframeworkSlice {
+                platform: "macosx".to_string(),
+                library_path: PathBuf::from("libexample-macos.a"),
+            },
+            XcframeworkSlice {
+                platform: "macosx".to_string(),
+                library_path: PathBuf::from("libexample-macos-2.a"),
+            },
+        ];
+        let err = create_xcframework(
+            Path::new("/nonexistent/component-out"),
+            &slices,
+            Path::new("/nonexistent/staging"),
+            Path::new("/nonexistent/out.xcframework"),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("macosx"));
+    }
+
+    #[test]
+    fn test_create_xcframework_rejects_empty_slices() {
+        let err = create_xcframework(
+            Path::new("/nonexistent/component-out"),
+            &[],
+            Path::new("/nonexistent/staging"),
+            Path::new("/nonexistent/out.xcframework"),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("at least one slice"));
+    }
+}